@@ -0,0 +1,164 @@
+//! ASN.1 DER <-> raw ECDSA signature conversion.
+//!
+//! OpenSSL signs and verifies ECDSA using a DER-encoded
+//! `SEQUENCE { r INTEGER, s INTEGER }`, but VAPID (like JWS in general)
+//! requires the raw, fixed-width `r || s` form. This used to be hand-rolled
+//! with hardcoded offsets that assumed single-byte lengths and 32-byte
+//! integers, which breaks whenever a length needs more than one byte or r/s
+//! is shorter than 32 bytes. This module parses/encodes the tags and
+//! lengths properly instead.
+
+use crate::error::{self, VapidErrorKind, VapidResult};
+
+const COORD_LEN: usize = 32;
+const INTEGER_TAG: u8 = 0x02;
+const SEQUENCE_TAG: u8 = 0x30;
+
+/// Convert a DER-encoded ECDSA signature into the raw, fixed 64-byte `r || s` form.
+pub fn der_to_raw(der: &[u8]) -> VapidResult<[u8; 64]> {
+    let mut pos = 0;
+    expect_tag(der, &mut pos, SEQUENCE_TAG)?;
+    let seq_len = read_len(der, &mut pos)?;
+    if der.len() < pos + seq_len {
+        return Err(parse_err("truncated ASN.1 sequence"));
+    }
+
+    let r = read_integer(der, &mut pos)?;
+    let s = read_integer(der, &mut pos)?;
+
+    let mut raw = [0u8; 64];
+    copy_padded(&r, &mut raw[..COORD_LEN])?;
+    copy_padded(&s, &mut raw[COORD_LEN..])?;
+    Ok(raw)
+}
+
+/// Convert a raw 64-byte `r || s` ECDSA signature into DER
+/// (`SEQUENCE { r INTEGER, s INTEGER }`).
+pub fn raw_to_der(raw: &[u8; 64]) -> Vec<u8> {
+    let mut body = encode_integer(&raw[..COORD_LEN]);
+    body.extend(encode_integer(&raw[COORD_LEN..]));
+
+    let mut out = vec![SEQUENCE_TAG];
+    out.extend(encode_len(body.len()));
+    out.extend(body);
+    out
+}
+
+fn parse_err(msg: &str) -> error::VapidError {
+    VapidErrorKind::Protocol(format!("Invalid ASN.1 signature: {}", msg)).into()
+}
+
+fn expect_tag(buf: &[u8], pos: &mut usize, tag: u8) -> VapidResult<()> {
+    if buf.get(*pos) != Some(&tag) {
+        return Err(parse_err("unexpected tag"));
+    }
+    *pos += 1;
+    Ok(())
+}
+
+/// Read a DER length, which is either a single byte (< 0x80) or a leading
+/// `0x80 | n` byte followed by `n` big-endian length bytes.
+fn read_len(buf: &[u8], pos: &mut usize) -> VapidResult<usize> {
+    let first = *buf.get(*pos).ok_or_else(|| parse_err("truncated length"))?;
+    *pos += 1;
+    if first & 0x80 == 0 {
+        return Ok(first as usize);
+    }
+    let n_bytes = (first & 0x7f) as usize;
+    let bytes = buf
+        .get(*pos..*pos + n_bytes)
+        .ok_or_else(|| parse_err("truncated length"))?;
+    *pos += n_bytes;
+    Ok(bytes.iter().fold(0usize, |len, b| (len << 8) | *b as usize))
+}
+
+fn read_integer(buf: &[u8], pos: &mut usize) -> VapidResult<Vec<u8>> {
+    expect_tag(buf, pos, INTEGER_TAG)?;
+    let len = read_len(buf, pos)?;
+    let bytes = buf
+        .get(*pos..*pos + len)
+        .ok_or_else(|| parse_err("truncated integer"))?;
+    *pos += len;
+    // Strip the leading sign-padding zero byte, if present.
+    let bytes = match bytes {
+        [0, rest @ ..] if !rest.is_empty() && rest[0] & 0x80 != 0 => rest,
+        _ => bytes,
+    };
+    Ok(bytes.to_vec())
+}
+
+/// Left-pad `value` with zeros to fill `dest` exactly.
+fn copy_padded(value: &[u8], dest: &mut [u8]) -> VapidResult<()> {
+    if value.len() > dest.len() {
+        return Err(parse_err("integer too large for raw signature"));
+    }
+    let pad = dest.len() - value.len();
+    dest[pad..].copy_from_slice(value);
+    Ok(())
+}
+
+fn encode_len(len: usize) -> Vec<u8> {
+    if len < 0x80 {
+        return vec![len as u8];
+    }
+    let bytes: Vec<u8> = len
+        .to_be_bytes()
+        .into_iter()
+        .skip_while(|&b| b == 0)
+        .collect();
+    let mut out = vec![0x80 | bytes.len() as u8];
+    out.extend(bytes);
+    out
+}
+
+/// Encode a 32-byte unsigned coordinate as a DER `INTEGER`: strip leading
+/// zero bytes, then prepend a single `0x00` only if the high bit of the
+/// leading byte would otherwise make the value look negative.
+fn encode_integer(coord: &[u8]) -> Vec<u8> {
+    let mut trimmed = coord;
+    while trimmed.len() > 1 && trimmed[0] == 0 {
+        trimmed = &trimmed[1..];
+    }
+
+    let mut value = Vec::with_capacity(trimmed.len() + 1);
+    if trimmed[0] & 0x80 != 0 {
+        value.push(0);
+    }
+    value.extend_from_slice(trimmed);
+
+    let mut out = vec![INTEGER_TAG];
+    out.extend(encode_len(value.len()));
+    out.extend(value);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let mut raw = [0u8; 64];
+        for (i, b) in raw.iter_mut().enumerate() {
+            *b = (i * 7 + 1) as u8;
+        }
+        let der = raw_to_der(&raw);
+        assert_eq!(der_to_raw(&der).unwrap(), raw);
+    }
+
+    #[test]
+    fn test_round_trip_high_bit_and_short_values() {
+        // r has its high bit set (needs a 0x00 pad byte in DER); s is short
+        // (needs left-padding with zeros when decoded back to 32 bytes).
+        let mut raw = [0u8; 64];
+        raw[0] = 0xff;
+        raw[63] = 0x01;
+        let der = raw_to_der(&raw);
+        assert_eq!(der_to_raw(&der).unwrap(), raw);
+    }
+
+    #[test]
+    fn test_der_to_raw_rejects_garbage() {
+        assert!(der_to_raw(&[0x00, 0x01, 0x02]).is_err());
+    }
+}
@@ -0,0 +1,80 @@
+//! Typed VAPID JWT claims.
+//!
+//! RFC 7519 requires `NumericDate` claims (`exp`, `iat`, `nbf`, ...) to be
+//! JSON numbers -- seconds since the UNIX epoch -- not strings. The original
+//! HashMap-based API let callers insert `"exp"` as a string, which `sign()`
+//! would then silently fail to read. `VapidClaims` gives the common fields a
+//! real type so that mistake can't compile, while `extra` still accepts any
+//! custom claim a caller wants to add.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::{VapidErrorKind, VapidResult};
+
+/// A typed VAPID claims set.
+///
+/// Build one with [`VapidClaims::new`] and the `aud`/`exp` builder methods,
+/// then pass it to [`crate::sign`] the same way you would a
+/// `HashMap<String, serde_json::Value>`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VapidClaims {
+    /// The contact for the application server, e.g. `mailto:admin@example.com`.
+    pub sub: String,
+    /// The push service's origin, e.g. `https://push.services.mozilla.com`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aud: Option<String>,
+    /// Expiration, in seconds since the UNIX epoch. Filled in by `sign()` if omitted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exp: Option<i64>,
+    /// Any additional claims the caller wants to include.
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+impl VapidClaims {
+    /// Start a new claims set for the given `sub` (contact) value.
+    pub fn new<S: Into<String>>(sub: S) -> Self {
+        VapidClaims {
+            sub: sub.into(),
+            aud: None,
+            exp: None,
+            extra: HashMap::new(),
+        }
+    }
+
+    /// Set the `aud` (audience) claim.
+    pub fn aud<S: Into<String>>(mut self, aud: S) -> Self {
+        self.aud = Some(aud.into());
+        self
+    }
+
+    /// Set the `exp` (expiration) claim, in seconds since the UNIX epoch.
+    pub fn exp(mut self, exp: i64) -> Self {
+        self.exp = Some(exp);
+        self
+    }
+
+    /// Add a custom claim.
+    pub fn insert<S: Into<String>>(mut self, key: S, value: Value) -> Self {
+        self.extra.insert(key.into(), value);
+        self
+    }
+
+    /// Convert into the `HashMap<String, Value>` representation `sign()` and
+    /// `verify()` use internally.
+    pub fn to_map(&self) -> VapidResult<HashMap<String, Value>> {
+        match serde_json::to_value(self)? {
+            Value::Object(map) => Ok(map.into_iter().collect()),
+            _ => Err(VapidErrorKind::Internal("claims did not serialize to an object".to_owned()).into()),
+        }
+    }
+
+    /// Reconstruct a `VapidClaims` from the loosely typed map `verify()` returns.
+    pub fn from_map(map: &HashMap<String, Value>) -> VapidResult<Self> {
+        let value = Value::Object(map.iter().map(|(k, v)| (k.clone(), v.clone())).collect());
+        Ok(serde_json::from_value(value)?)
+    }
+}
@@ -30,6 +30,18 @@ pub enum VapidErrorKind {
     /// An invalid public key was specified. Is it EC Prime256v1?
     #[error("Invalid public key")]
     PublicKey,
+    /// The Authorization header, base64, or claims JSON could not be parsed.
+    #[error("Parse error: {}", .0)]
+    Parse(String),
+    /// The token's `exp` claim is in the past (beyond the configured leeway).
+    #[error("Token has expired")]
+    ExpiredToken,
+    /// The token's `nbf` claim is in the future (beyond the configured leeway).
+    #[error("Token is not yet valid")]
+    ImmatureToken,
+    /// The token's `aud` claim did not match any of the accepted audiences.
+    #[error("Token audience is not accepted")]
+    InvalidAudience,
     /// A vapid error occurred.
     #[error("VAPID error: {}", .0)]
     Protocol(String),
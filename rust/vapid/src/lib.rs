@@ -29,23 +29,52 @@
 //! let authorization_header = sign(my_key, &mut claims).unwrap();
 //!
 //! ```
+//!
+//! New code can use the typed [`VapidClaims`] instead of the raw HashMap:
+//! ```rust,no_run
+//! use vapid::{Key, VapidClaims, sign};
+//!
+//! let my_key = Key::from_pem("pem/file/path.pem").unwrap();
+//! let claims = VapidClaims::new("mailto:bob@example.com").aud("https://host.ext");
+//! let authorization_header = sign(my_key, claims).unwrap();
+//! ```
+//!
+//! VAPID only signs the `Authorization` header; to encrypt the payload
+//! itself (RFC 8188 `aes128gcm`), enable the `encrypt` feature and use
+//! [`encrypt`].
 
 use std::time::SystemTime;
 
 use std::collections::HashMap;
+use std::convert::TryInto;
 use std::fs;
 use std::hash::BuildHasher;
 use std::path::Path;
 
 use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
-use openssl::bn::BigNumContext;
+use openssl::bn::{BigNum, BigNumContext};
 use openssl::ec::{self, EcKey};
 use openssl::hash::MessageDigest;
 use openssl::nid;
 use openssl::pkey::{PKey, Private, Public};
 use openssl::sign::{Signer, Verifier};
 
+mod asn1;
+mod claims;
+// NOTE: this needs `[features] encrypt = []` declared in this crate's
+// Cargo.toml, plus the `openssl`/`base64` deps it uses, or `--features
+// encrypt` has nothing to select and this module is unreachable. This
+// source tree doesn't carry a Cargo.toml to add that entry to; wire it in
+// wherever the crate's real manifest lives.
+#[cfg(feature = "encrypt")]
+mod encrypt;
 mod error;
+mod validation;
+
+pub use claims::VapidClaims;
+#[cfg(feature = "encrypt")]
+pub use encrypt::encrypt;
+pub use validation::Validation;
 
 /// a Key is a helper for creating or using a VAPID EC key.
 ///
@@ -88,41 +117,163 @@ impl Key {
         Ok(Key { key })
     }
 
-    /// Convert the private key into a base64 string
-    pub fn to_private_raw(&self) -> String {
-        // Return the private key as a raw bit array
+    /// Convert the private key into a base64 string.
+    ///
+    /// The scalar is encoded as a fixed-width 32-byte big-endian field
+    /// (zero-padded), matching what `from_private_raw()` expects -- a plain
+    /// `to_vec()` strips leading zero bytes, which would silently produce a
+    /// shorter encoding for roughly 1 in 256 keys and break the round trip.
+    pub fn to_private_raw(&self) -> error::VapidResult<String> {
         let key = self.key.private_key();
-        BASE64_URL_SAFE_NO_PAD.encode(&key.to_vec())
+        Ok(BASE64_URL_SAFE_NO_PAD.encode(&key.to_vec_padded(32)?))
     }
 
     /// Convert the public key into a uncompressed, raw base64 string
-    pub fn to_public_raw(&self) -> String {
+    pub fn to_public_raw(&self) -> error::VapidResult<String> {
         //Return the public key as a raw bit array
-        let mut ctx = BigNumContext::new().unwrap();
-        let group = ec::EcGroup::from_curve_name(Key::name()).unwrap();
+        let mut ctx = BigNumContext::new()?;
+        let group = ec::EcGroup::from_curve_name(Key::name())?;
 
         let key = self.key.public_key();
-        let keybytes = key
-            .to_bytes(&group, ec::PointConversionForm::UNCOMPRESSED, &mut ctx)
-            .unwrap();
-        BASE64_URL_SAFE_NO_PAD.encode(&keybytes)
+        let keybytes = key.to_bytes(&group, ec::PointConversionForm::UNCOMPRESSED, &mut ctx)?;
+        Ok(BASE64_URL_SAFE_NO_PAD.encode(keybytes))
+    }
+
+    /// Reconstruct a private key from the raw base64 string produced by `to_private_raw()`.
+    ///
+    /// Decodes the 32-byte P-256 private scalar, checks it against the curve
+    /// order, and recomputes the matching public point (`generator * scalar`)
+    /// so the result is a complete, usable `EcKey`.
+    pub fn from_private_raw(s: &str) -> error::VapidResult<Key> {
+        let bytes = BASE64_URL_SAFE_NO_PAD
+            .decode(s.as_bytes())
+            .map_err(|_| error::VapidErrorKind::PublicKey)?;
+        if bytes.len() != 32 {
+            return Err(error::VapidErrorKind::PublicKey.into());
+        }
+        let scalar = BigNum::from_slice(&bytes)?;
+
+        let group = ec::EcGroup::from_curve_name(Key::name())?;
+        let mut ctx = BigNumContext::new()?;
+        let mut order = BigNum::new()?;
+        group.order(&mut order, &mut ctx)?;
+        let one = BigNum::from_u32(1)?;
+        if scalar < one || scalar >= order {
+            return Err(error::VapidErrorKind::PublicKey.into());
+        }
+
+        let mut point = ec::EcPoint::new(&group)?;
+        point.mul_generator(&group, &scalar, &mut ctx)?;
+
+        Ok(Key {
+            key: EcKey::from_private_components(&group, &scalar, &point)?,
+        })
     }
 
     /// Read the public key from an uncompressed, raw base64 string
     pub fn from_public_raw(bits: String) -> error::VapidResult<ec::EcKey<Public>> {
         //Read a public key from a raw bit array
-        let bytes: Vec<u8> = BASE64_URL_SAFE_NO_PAD.decode(&bits.into_bytes()).unwrap();
-        let mut ctx = BigNumContext::new().unwrap();
+        let bytes: Vec<u8> = BASE64_URL_SAFE_NO_PAD
+            .decode(bits.as_bytes())
+            .map_err(|err| error::VapidErrorKind::Parse(format!("Invalid base64 key: {}", err)))?;
+        let mut ctx = BigNumContext::new()?;
         let group = ec::EcGroup::from_curve_name(nid::Nid::X9_62_PRIME256V1)?;
-        if bytes.len() != 65 || bytes[0] != 4 {
-            // It's not a properly tagged key.
+        let point = point_from_uncompressed(&group, &bytes, &mut ctx)?;
+        Ok(ec::EcKey::from_public_key(&group, &point)?)
+    }
+
+    /// Export this key as a JWK (RFC 7517) object:
+    /// `{"kty":"EC","crv":"P-256","x":...,"y":...,"d":...}`, all coordinates
+    /// base64url-encoded. This mirrors the format OIDC/JWKS tooling publishes
+    /// at a `jwks_uri`, so a VAPID key can be exchanged with any JWKS-based
+    /// infrastructure or served at a `.well-known` endpoint.
+    pub fn to_jwk(&self) -> error::VapidResult<serde_json::Value> {
+        let mut ctx = BigNumContext::new()?;
+        let group = ec::EcGroup::from_curve_name(Key::name())?;
+        let pub_bytes =
+            self.key
+                .public_key()
+                .to_bytes(&group, ec::PointConversionForm::UNCOMPRESSED, &mut ctx)?;
+        let (x, y) = (&pub_bytes[1..33], &pub_bytes[33..65]);
+        // RFC 7518 §6.2.2.1 requires `d` to be the fixed field size (32
+        // bytes for P-256); a plain `to_vec()` strips leading zero bytes
+        // and would produce a short, non-conformant encoding for roughly
+        // 1 in 256 keys.
+        let d = self.key.private_key().to_vec_padded(32)?;
+
+        Ok(serde_json::json!({
+            "kty": "EC",
+            "crv": "P-256",
+            "x": BASE64_URL_SAFE_NO_PAD.encode(x),
+            "y": BASE64_URL_SAFE_NO_PAD.encode(y),
+            "d": BASE64_URL_SAFE_NO_PAD.encode(d),
+        }))
+    }
+
+    /// Import a private key from a JWK (RFC 7517) object produced by `to_jwk()`.
+    pub fn from_jwk(jwk: &serde_json::Value) -> error::VapidResult<Key> {
+        let kty = jwk.get("kty").and_then(|v| v.as_str());
+        let crv = jwk.get("crv").and_then(|v| v.as_str());
+        if kty != Some("EC") || crv != Some("P-256") {
             return Err(error::VapidErrorKind::PublicKey.into());
         }
-        let point = ec::EcPoint::from_bytes(&group, &bytes, &mut ctx)?;
-        Ok(ec::EcKey::from_public_key(&group, &point)?)
+        let x = jwk_coord(jwk, "x")?;
+        let y = jwk_coord(jwk, "y")?;
+        // `d` (the private scalar) is optional in a general JWK, but `Key`
+        // always represents a private key, so it's required here.
+        let d = jwk
+            .get("d")
+            .and_then(|v| v.as_str())
+            .ok_or(error::VapidErrorKind::PublicKey)?;
+        let d = BASE64_URL_SAFE_NO_PAD
+            .decode(d.as_bytes())
+            .map_err(|_| error::VapidErrorKind::PublicKey)?;
+
+        let mut point_bytes = Vec::with_capacity(65);
+        point_bytes.push(4u8);
+        point_bytes.extend_from_slice(&x);
+        point_bytes.extend_from_slice(&y);
+
+        let group = ec::EcGroup::from_curve_name(Key::name())?;
+        let mut ctx = BigNumContext::new()?;
+        let point = point_from_uncompressed(&group, &point_bytes, &mut ctx)?;
+        let scalar = BigNum::from_slice(&d)?;
+
+        Ok(Key {
+            key: EcKey::from_private_components(&group, &scalar, &point)?,
+        })
     }
 }
 
+/// Validate and parse a 65-byte uncompressed EC point (`0x04 || x || y`),
+/// shared by `from_public_raw()` and `from_jwk()`.
+fn point_from_uncompressed(
+    group: &ec::EcGroupRef,
+    bytes: &[u8],
+    ctx: &mut BigNumContext,
+) -> error::VapidResult<ec::EcPoint> {
+    if bytes.len() != 65 || bytes[0] != 4 {
+        // It's not a properly tagged key.
+        return Err(error::VapidErrorKind::PublicKey.into());
+    }
+    Ok(ec::EcPoint::from_bytes(group, bytes, ctx)?)
+}
+
+/// Decode a base64url JWK coordinate (`x`/`y`), validating it's exactly 32 bytes.
+fn jwk_coord(jwk: &serde_json::Value, name: &str) -> error::VapidResult<Vec<u8>> {
+    let raw = jwk
+        .get(name)
+        .and_then(|v| v.as_str())
+        .ok_or(error::VapidErrorKind::PublicKey)?;
+    let bytes = BASE64_URL_SAFE_NO_PAD
+        .decode(raw.as_bytes())
+        .map_err(|_| error::VapidErrorKind::PublicKey)?;
+    if bytes.len() != 32 {
+        return Err(error::VapidErrorKind::PublicKey.into());
+    }
+    Ok(bytes)
+}
+
 /// The elements of the Authentication.
 #[derive(Debug)]
 struct AuthElements {
@@ -133,39 +284,55 @@ struct AuthElements {
 }
 
 /// Parse the Authorization Header for useful things.
-fn parse_auth_token(auth_token: &str) -> Result<AuthElements, String> {
+fn parse_auth_token(auth_token: &str) -> error::VapidResult<AuthElements> {
+    fn parse_err(msg: impl Into<String>) -> error::VapidError {
+        error::VapidErrorKind::Parse(msg.into()).into()
+    }
+
     let mut parts: Vec<&str> = auth_token.split(' ').collect();
+    if parts.is_empty() {
+        return Err(parse_err("Authorization header is empty"));
+    }
     let mut schema = parts.remove(0).to_lowercase();
     // Ignore the first token if it's the header line.
     if schema == "authorization:" {
+        if parts.is_empty() {
+            return Err(parse_err("Authorization header is missing a schema"));
+        }
         schema = parts.remove(0).to_lowercase();
     }
     let mut reply: AuthElements = AuthElements {
         t: Vec::new(),
         k: String::new(),
     };
-    match schema.to_lowercase().as_ref() {
+    let body = parts
+        .first()
+        .ok_or_else(|| parse_err("Authorization header is missing its token list"))?;
+    match schema.as_ref() {
         "vapid" => {
-            for kvi in parts[0].splitn(2, ',') {
+            for kvi in body.splitn(2, ',') {
                 let kv: Vec<String> = kvi.splitn(2, '=').map(String::from).collect();
+                let value = kv
+                    .get(1)
+                    .ok_or_else(|| parse_err(format!("Malformed '{}' token", kv[0])))?;
                 match kv[0].to_lowercase().as_ref() {
                     "t" => {
-                        let ts: Vec<String> = kv[1].split('.').map(String::from).collect();
+                        let ts: Vec<String> = value.split('.').map(String::from).collect();
                         if ts.len() != 3 {
-                            return Err("Invalid t token specified".into());
+                            return Err(parse_err("Invalid t token specified"));
                         }
                         let ttoken = format!("{}.{}", ts[0], ts[1]);
                         reply.t = vec![ttoken, ts[2].clone()];
                     }
-                    "k" => reply.k = kv[1].clone(),
+                    "k" => reply.k = value.clone(),
                     _ => {}
                 }
             }
         }
         "webpush" => {
-            reply.t = parts[0].split('.').map(String::from).collect();
+            reply.t = body.split('.').map(String::from).collect();
         }
-        _ => return Err(format!("Unknown schema type: {}", parts[0])),
+        _ => return Err(parse_err(format!("Unknown schema type: {}", schema))),
     };
     Ok(reply)
 }
@@ -179,18 +346,75 @@ fn to_secs(t: SystemTime) -> u64 {
         .as_secs()
 }
 
-/// Convert the HashMap containing the claims into an Authorization header.
+/// Anything that `sign()` can turn into a claims set: either the typed
+/// [`VapidClaims`] or the original `HashMap<String, serde_json::Value>`.
+pub trait SignClaims {
+    /// Produce an owned `HashMap` of claims for `sign()` to validate and encode.
+    fn into_claims_map(&self) -> error::VapidResult<HashMap<String, serde_json::Value>>;
+
+    /// Called after signing with the final claims (including any `exp` that
+    /// `sign()` filled in). The `HashMap` impl uses this to write the
+    /// auto-filled `exp` back into the caller's map, matching the original
+    /// in-place behavior; other claim types are read-only here by default.
+    fn write_back(&mut self, _claims: &HashMap<String, serde_json::Value>) {}
+}
+
+impl<S: BuildHasher> SignClaims for &mut HashMap<String, serde_json::Value, S> {
+    fn into_claims_map(&self) -> error::VapidResult<HashMap<String, serde_json::Value>> {
+        Ok(self.iter().map(|(k, v)| (k.clone(), v.clone())).collect())
+    }
+
+    fn write_back(&mut self, claims: &HashMap<String, serde_json::Value>) {
+        if let Some(exp) = claims.get("exp") {
+            self.entry(String::from("exp")).or_insert_with(|| exp.clone());
+        }
+    }
+}
+
+impl SignClaims for VapidClaims {
+    fn into_claims_map(&self) -> error::VapidResult<HashMap<String, serde_json::Value>> {
+        self.to_map()
+    }
+}
+
+impl SignClaims for &VapidClaims {
+    fn into_claims_map(&self) -> error::VapidResult<HashMap<String, serde_json::Value>> {
+        self.to_map()
+    }
+}
+
+/// Read a `NumericDate` claim (RFC 7519 §4.1.4): it must be an integer JSON
+/// number (not a string or a fraction), or `sign()` would otherwise silently
+/// accept an unusable value.
+fn numeric_claim(
+    claims: &HashMap<String, serde_json::Value>,
+    name: &str,
+) -> error::VapidResult<Option<i64>> {
+    match claims.get(name) {
+        Some(val) => val.as_i64().map(Some).ok_or_else(|| {
+            error::VapidErrorKind::Protocol(format!(r#""{}" must be a JSON integer"#, name)).into()
+        }),
+        None => Ok(None),
+    }
+}
+
+/// Convert the claims into an Authorization header.
+///
+/// `claims` may be a [`VapidClaims`] or, for compatibility, a
+/// `HashMap<String, serde_json::Value>` (passed by `&mut` reference, as
+/// before). When passed a `HashMap`, any claim `sign()` fills in itself
+/// (namely `exp`, when omitted) is written back into the caller's map, as
+/// in the original API.
 /// `key` must be generated or initialized before this is used. See `Key::from_pem()` or
 /// `Key::generate()`.
-pub fn sign<S: BuildHasher>(
-    key: Key,
-    claims: &mut HashMap<String, serde_json::Value, S>,
-) -> error::VapidResult<String> {
+pub fn sign<C: SignClaims>(key: Key, mut claims: C) -> error::VapidResult<String> {
     // this is the common, static header for all VAPID JWT objects.
     let prefix: String = "{\"typ\":\"JWT\",\"alg\":\"ES256\"}".into();
 
+    let mut claim_map = claims.into_claims_map()?;
+
     // Check the claims
-    match claims.get("sub") {
+    match claim_map.get("sub") {
         Some(sub) => {
             if !sub.as_str().unwrap().starts_with("mailto") {
                 return Err(error::VapidErrorKind::Protocol(
@@ -205,12 +429,11 @@ pub fn sign<S: BuildHasher>(
     }
     let today = SystemTime::now();
     let tomorrow = today + time::Duration::hours(24);
-    claims
+    claim_map
         .entry(String::from("exp"))
         .or_insert_with(|| serde_json::Value::from(to_secs(tomorrow)));
-    match claims.get("exp") {
-        Some(exp) => {
-            let exp_val = exp.as_i64().unwrap();
+    match numeric_claim(&claim_map, "exp")? {
+        Some(exp_val) => {
             if (exp_val as u64) < to_secs(today) {
                 return Err(
                     error::VapidErrorKind::Protocol(r#""exp" already expired"#.to_owned()).into(),
@@ -231,14 +454,17 @@ pub fn sign<S: BuildHasher>(
             .into());
         }
     }
+    // Mirror any auto-filled claims (namely `exp`) back into the caller's
+    // map, matching the original in-place-mutation behavior of `sign()`.
+    claims.write_back(&claim_map);
 
-    let json: String = serde_json::to_string(&claims)?;
+    let json: String = serde_json::to_string(&claim_map)?;
     let content = format!(
         "{}.{}",
         BASE64_URL_SAFE_NO_PAD.encode(&prefix),
         BASE64_URL_SAFE_NO_PAD.encode(&json),
     );
-    let auth_k = key.to_public_raw();
+    let auth_k = key.to_public_raw()?;
     let pub_key = PKey::from_ec_key(key.key)?;
 
     let mut signer = match Signer::new(MessageDigest::sha256(), &pub_key) {
@@ -256,33 +482,14 @@ pub fn sign<S: BuildHasher>(
         .expect("Could not encode data for signature");
     let signature = signer.sign_to_vec().expect("Could not finalize signature");
 
-    // Decode signature BER to r,s pair
-    let r_off: usize = 3;
-    // r_len must be > 33. Not checking here because if this ever breaks, we have LOTS of other
-    // problems.
-    let r_len = signature[r_off] as usize;
-    // calculate the offsets for the byte array data we want.
-    let s_off: usize = r_off + r_len + 2;
-    let s_len = signature[s_off] as usize;
-    let mut r_val = &signature[(r_off + 1)..(r_off + 1 + r_len)];
-    let mut s_val = &signature[(s_off + 1)..(s_off + 1 + s_len)];
-    // Strip the leading 0 if it's present.
-    if r_len == 33 && r_val[0] == 0 {
-        r_val = &r_val[1..];
-    }
-    if s_len == 33 && s_val[0] == 0 {
-        s_val = &s_val[1..];
-    }
-    // we now have the r and s byte arrays. Build the raw RS we need for the signature
-    // println!("r_val: ({}){:?}\ns_val: ({}){:?} ", r_val.len(), r_val, s_val.len(), s_val);
-    let mut sigval: Vec<u8> = Vec::with_capacity(64);
-    sigval.extend(r_val);
-    sigval.extend(s_val);
+    // OpenSSL signs in DER (SEQUENCE of two INTEGERs); VAPID wants the raw,
+    // fixed-width `r || s` form.
+    let sigval = asn1::der_to_raw(&signature)?;
 
     let auth_t = format!(
         "{}.{}",
         content,
-        BASE64_URL_SAFE_NO_PAD.encode(unsafe { &String::from_utf8_unchecked(sigval) },)
+        BASE64_URL_SAFE_NO_PAD.encode(sigval),
     );
 
     Ok(format!(
@@ -293,73 +500,92 @@ pub fn sign<S: BuildHasher>(
 
 /// Verify that the auth token string matches for the verification token string
 pub fn verify(auth_token: String) -> Result<HashMap<String, serde_json::Value>, String> {
-    let auth_token = parse_auth_token(&auth_token).expect("Authorization header is invalid.");
-    let pub_ec_key =
-        Key::from_public_raw(auth_token.k).expect("'k' token is not a valid public key");
-    let pub_key = &match PKey::from_ec_key(pub_ec_key) {
-        Ok(key) => key,
-        Err(err) => return Err(format!("Public Key Generation error: {:?}", err)),
-    };
-    let mut verifier = match Verifier::new(MessageDigest::sha256(), pub_key) {
-        Ok(verifier) => verifier,
-        Err(err) => return Err(format!("Verifier failed to initialize: {:?}", err)),
-    };
+    verify_with(auth_token, &Validation::none()).map_err(|err| format!("{:?}", err))
+}
 
-    let data = &auth_token.t[0].clone().into_bytes();
+/// Verify the auth token string, additionally enforcing `validation` against
+/// the decoded claims (expiration, not-before, and/or accepted audiences).
+pub fn verify_with(
+    auth_token: String,
+    validation: &Validation,
+) -> error::VapidResult<HashMap<String, serde_json::Value>> {
+    let auth_token = parse_auth_token(&auth_token)?;
+    let pub_ec_key = Key::from_public_raw(auth_token.k)?;
+    let pub_key = &PKey::from_ec_key(pub_ec_key)?;
+    let mut verifier = Verifier::new(MessageDigest::sha256(), pub_key)?;
+
+    let content = auth_token
+        .t
+        .first()
+        .ok_or_else(|| error::VapidErrorKind::Parse("Missing JWT content".to_owned()))?;
+    let sig_b64 = auth_token
+        .t
+        .get(1)
+        .ok_or_else(|| error::VapidErrorKind::Parse("Missing JWT signature".to_owned()))?;
     let verif_sig = BASE64_URL_SAFE_NO_PAD
-        .decode(&auth_token.t[1].clone().into_bytes())
-        .expect("Signature failed to decode from base64");
-    verifier
-        .update(data)
-        .expect("Data failed to load into verifier");
-
-    // Extract the values from the combined raw key.
-    let mut r_val = Vec::with_capacity(32);
-    let mut s_val = Vec::with_capacity(32);
-    r_val.extend(verif_sig[0..32].iter());
-    s_val.extend(verif_sig[32..].iter());
-
-    /* Compose the sequence DER by hand, because the current rust libraries lack this. */
-    // write r & s as asn1
-    // Prefix is the "\x02" + the length. We can cheat here because we know how long the keys are.
-    let mut r_asn = vec![2];
-    let mut s_asn = vec![2];
-    // check if we need to pad for high order byte
-    if r_val[0] > 127 {
-        r_asn.extend_from_slice(&[33, 0])
-    } else {
-        r_asn.extend_from_slice(&[32])
-    }
-    r_asn.append(&mut r_val);
-    if s_val[0] > 127 {
-        s_asn.extend_from_slice(&[33, 0])
-    } else {
-        s_asn.extend_from_slice(&[32])
-    }
-    s_asn.append(&mut s_val);
-
-    // seq = "\x30" + (len(rs) + len(ss)) + rs + ss
-    let mut seq: Vec<u8> = vec![48];
-    seq.append(&mut vec![(r_asn.len() + s_asn.len()) as u8]);
-    seq.append(&mut r_asn);
-    seq.append(&mut s_asn);
-
-    match verifier.verify(&seq) {
-        Ok(true) => {
-            // Success! Return the decoded claims.
-            let token = auth_token.t[0].clone();
-            let claim_data: Vec<&str> = token.split('.').collect();
-            let bytes = BASE64_URL_SAFE_NO_PAD
-                .decode(&claim_data[1])
-                .expect("Claims were not properly base64 encoded");
-            Ok(serde_json::from_str(
-                &String::from_utf8(bytes)
-                    .expect("Claims included an invalid character and could not be decoded."),
-            )
-            .expect("Claims are not valid JSON"))
+        .decode(sig_b64)
+        .map_err(|err| error::VapidErrorKind::Parse(format!("Invalid signature base64: {}", err)))?;
+    verifier.update(content.as_bytes())?;
+
+    // The raw signature is `r || s`; OpenSSL's verifier wants it back in DER.
+    let raw_sig: [u8; 64] = verif_sig
+        .try_into()
+        .map_err(|_| error::VapidErrorKind::Protocol("Signature is not 64 bytes".to_owned()))?;
+    let seq = asn1::raw_to_der(&raw_sig);
+
+    match verifier.verify(&seq)? {
+        true => {
+            // Success! Decode the claims and apply the requested validation.
+            let claim_data: Vec<&str> = content.split('.').collect();
+            let claims_b64 = claim_data.get(1).ok_or_else(|| {
+                error::VapidErrorKind::Parse("Missing claims segment".to_owned())
+            })?;
+            let bytes = BASE64_URL_SAFE_NO_PAD.decode(claims_b64).map_err(|err| {
+                error::VapidErrorKind::Parse(format!("Invalid claims base64: {}", err))
+            })?;
+            let claims_json = String::from_utf8(bytes).map_err(|err| {
+                error::VapidErrorKind::Parse(format!("Claims are not valid UTF-8: {}", err))
+            })?;
+            let claims: HashMap<String, serde_json::Value> = serde_json::from_str(&claims_json)?;
+
+            let now = to_secs(SystemTime::now());
+            if validation.validate_exp {
+                match numeric_claim(&claims, "exp")? {
+                    Some(exp) => {
+                        if (exp as u64) + validation.leeway < now {
+                            return Err(error::VapidErrorKind::ExpiredToken.into());
+                        }
+                    }
+                    // No `exp` to check is no different from one that's
+                    // already passed: a caller requiring expiration
+                    // enforcement must not accept a token that never expires.
+                    None => return Err(error::VapidErrorKind::ExpiredToken.into()),
+                }
+            }
+            if validation.validate_nbf {
+                if let Some(nbf) = numeric_claim(&claims, "nbf")? {
+                    if (nbf as u64).saturating_sub(validation.leeway) > now {
+                        return Err(error::VapidErrorKind::ImmatureToken.into());
+                    }
+                }
+            }
+            if validation.validate_iat {
+                if let Some(iat) = numeric_claim(&claims, "iat")? {
+                    if (iat as u64).saturating_sub(validation.leeway) > now {
+                        return Err(error::VapidErrorKind::ImmatureToken.into());
+                    }
+                }
+            }
+            if let Some(accepted) = &validation.aud {
+                let aud = claims.get("aud").and_then(|v| v.as_str());
+                if !aud.map(|aud| accepted.contains(aud)).unwrap_or(false) {
+                    return Err(error::VapidErrorKind::InvalidAudience.into());
+                }
+            }
+
+            Ok(claims)
         }
-        Ok(false) => Err("Verify failed".to_string()),
-        Err(err) => Err(format!("Verify failed {:?}", err)),
+        false => Err(error::VapidErrorKind::Protocol("Verify failed".to_owned()).into()),
     }
 }
 
@@ -434,6 +660,22 @@ mod tests {
         verify(vresult).expect("Signed claims failed to self verify");
     }
 
+    #[test]
+    fn test_sign_writes_back_exp() {
+        // sign() fills in "exp" when the caller omits it; for the HashMap
+        // API that auto-filled value should be written back into the
+        // caller's map, matching the original in-place behavior.
+        let key = Key::generate().unwrap();
+        let mut claims: HashMap<String, serde_json::Value> = HashMap::new();
+        claims.insert(
+            String::from("sub"),
+            serde_json::Value::from("mailto:mail@example.com"),
+        );
+        assert!(!claims.contains_key("exp"));
+        sign(key, &mut claims).unwrap();
+        assert!(claims.contains_key("exp"));
+    }
+
     // TODO: Test fail cases, verification, values
 
     #[test]
@@ -486,5 +728,195 @@ mod tests {
         assert!(test_claims() == verify(test_header).unwrap())
     }
 
+    #[test]
+    fn test_verify_with_expired_token() {
+        // That fixture's "exp" is a string well in the past; `verify()` never
+        // checked it, but `verify_with()` should reject it once asked to.
+        let test_header = [
+            "Authorization: vapid t=eyJ0eXAiOiJKV1QiLCJhbGciOiJFUzI1NiJ9.eyJhdWQiOiJodHRwcz\
+             ovL3B1c2guc2VydmljZXMubW96aWxsYS5jb20iLCJleHAiOiIxNDYzMDAxMzQwIiwic3ViIjoibWFp\
+             bHRvOmFkbWluQGV4YW1wbGUuY29tIn0.4ZiULZaqZ8_7Cf2UYu7KO3eGaqZL5d4RZ6pwBvR0rcmTho\
+             4WryVuZLfN-iMsHJ6Oc-4hkEZsMj8_32sXYSvTyg,k=BPD3F0hvy3Df69tjqRBN0ad08WH2nfaaxnp\
+             kuIO6BV9Pa7p8xA8GauX0R_S-D-k82kcTNsCiJ6ML-zJisBpyybs",
+        ]
+        .join("");
+        // The fixture's `exp` is a string, so enforcing it surfaces the bad
+        // claim type rather than a stale-timestamp error -- either way, it's
+        // no longer silently accepted.
+        match verify_with(test_header, &Validation::new()) {
+            Err(_) => {}
+            Ok(_) => panic!("Expected validation to reject the fixture token"),
+        }
+    }
+
+    #[test]
+    fn test_verify_with_rejects_missing_exp() {
+        // `sign()` always fills in `exp`, so craft a token without one by
+        // hand to exercise the gap: a caller that asked for expiration
+        // enforcement must not accept a token that has no `exp` at all.
+        let key = Key::generate().unwrap();
+        let mut claims: HashMap<String, serde_json::Value> = HashMap::new();
+        claims.insert(
+            String::from("sub"),
+            serde_json::Value::from("mailto:mail@example.com"),
+        );
+        let prefix = "{\"typ\":\"JWT\",\"alg\":\"ES256\"}";
+        let json = serde_json::to_string(&claims).unwrap();
+        let content = format!(
+            "{}.{}",
+            BASE64_URL_SAFE_NO_PAD.encode(prefix),
+            BASE64_URL_SAFE_NO_PAD.encode(&json),
+        );
+        let auth_k = key.to_public_raw().unwrap();
+        let pub_key = PKey::from_ec_key(key.key).unwrap();
+        let mut signer = Signer::new(MessageDigest::sha256(), &pub_key).unwrap();
+        signer.update(content.as_bytes()).unwrap();
+        let signature = signer.sign_to_vec().unwrap();
+        let sigval = asn1::der_to_raw(&signature).unwrap();
+        let auth_t = format!("{}.{}", content, BASE64_URL_SAFE_NO_PAD.encode(sigval));
+        let test_header = format!("Authorization: vapid t={},k={}", auth_t, auth_k);
+
+        match verify_with(test_header, &Validation::new()) {
+            Err(err) => assert!(matches!(err.kind(), error::VapidErrorKind::ExpiredToken)),
+            Ok(_) => panic!("Expected token without exp to be rejected"),
+        }
+    }
+
+    #[test]
+    fn test_verify_with_audience() {
+        let key = Key::generate().unwrap();
+        let claims = VapidClaims::new("mailto:mail@example.com").aud("https://push.example.com");
+        let result = sign(key, claims).unwrap();
+
+        let validation = Validation::new().audience(["https://push.example.com".to_string()]);
+        verify_with(result.clone(), &validation).expect("accepted audience should verify");
+
+        let validation = Validation::new().audience(["https://other.example.com".to_string()]);
+        match verify_with(result, &validation) {
+            Err(err) => assert!(matches!(err.kind(), error::VapidErrorKind::InvalidAudience)),
+            Ok(_) => panic!("Expected unaccepted audience to be rejected"),
+        }
+    }
+
+    #[test]
+    fn test_verify_malformed_header_does_not_panic() {
+        match verify("not a real header".to_string()) {
+            Err(_) => {}
+            Ok(_) => panic!("Expected malformed header to be rejected, not panic"),
+        }
+    }
+
+    #[test]
+    fn test_private_raw_round_trip() {
+        let key = Key::generate().unwrap();
+        let public_before = key.to_public_raw().unwrap();
+
+        let raw = key.to_private_raw().unwrap();
+        // Always fixed-width, so `from_private_raw()` can't reject it for
+        // being short -- a stripped leading zero byte used to make this
+        // flaky for about 1 in 256 generated keys.
+        assert_eq!(BASE64_URL_SAFE_NO_PAD.decode(&raw).unwrap().len(), 32);
+        let reloaded = Key::from_private_raw(&raw).unwrap();
+
+        assert_eq!(reloaded.to_public_raw().unwrap(), public_before);
+    }
+
+    #[test]
+    fn test_from_private_raw_rejects_bad_length() {
+        let short = BASE64_URL_SAFE_NO_PAD.encode([0u8; 31]);
+        let long = BASE64_URL_SAFE_NO_PAD.encode([0u8; 33]);
+        assert!(matches!(
+            Key::from_private_raw(&short).unwrap_err().kind(),
+            error::VapidErrorKind::PublicKey
+        ));
+        assert!(matches!(
+            Key::from_private_raw(&long).unwrap_err().kind(),
+            error::VapidErrorKind::PublicKey
+        ));
+    }
+
+    #[test]
+    fn test_from_private_raw_rejects_out_of_range_scalar() {
+        // Zero is below the valid [1, n-1] range for the P-256 scalar.
+        let zero = BASE64_URL_SAFE_NO_PAD.encode([0u8; 32]);
+        assert!(matches!(
+            Key::from_private_raw(&zero).unwrap_err().kind(),
+            error::VapidErrorKind::PublicKey
+        ));
+
+        // The P-256 group order n; any scalar >= n is out of range.
+        let order = [
+            0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+            0xff, 0xff, 0xbc, 0xe6, 0xfa, 0xad, 0xa7, 0x17, 0x9e, 0x84, 0xf3, 0xb9, 0xca, 0xc2,
+            0xfc, 0x63, 0x25, 0x51,
+        ];
+        let at_order = BASE64_URL_SAFE_NO_PAD.encode(order);
+        assert!(matches!(
+            Key::from_private_raw(&at_order).unwrap_err().kind(),
+            error::VapidErrorKind::PublicKey
+        ));
+    }
+
+    #[test]
+    fn test_jwk_round_trip() {
+        let key = Key::generate().unwrap();
+        let jwk = key.to_jwk().unwrap();
+        // `d` must be the fixed P-256 field size per RFC 7518 -- a stripped
+        // leading zero byte used to make this short for about 1 in 256 keys.
+        let d = jwk.get("d").and_then(|v| v.as_str()).unwrap();
+        assert_eq!(BASE64_URL_SAFE_NO_PAD.decode(d).unwrap().len(), 32);
+        let reloaded = Key::from_jwk(&jwk).unwrap();
+
+        assert_eq!(reloaded.to_private_raw().unwrap(), key.to_private_raw().unwrap());
+        assert_eq!(reloaded.to_public_raw().unwrap(), key.to_public_raw().unwrap());
+    }
+
+    #[test]
+    fn test_from_jwk_rejects_wrong_kty_or_crv() {
+        let key = Key::generate().unwrap();
+        let mut jwk = key.to_jwk().unwrap();
+        jwk["kty"] = serde_json::Value::from("RSA");
+        assert!(matches!(
+            Key::from_jwk(&jwk).unwrap_err().kind(),
+            error::VapidErrorKind::PublicKey
+        ));
+
+        let mut jwk = key.to_jwk().unwrap();
+        jwk["crv"] = serde_json::Value::from("P-384");
+        assert!(matches!(
+            Key::from_jwk(&jwk).unwrap_err().kind(),
+            error::VapidErrorKind::PublicKey
+        ));
+    }
+
+    #[test]
+    fn test_from_jwk_rejects_missing_d() {
+        let key = Key::generate().unwrap();
+        let mut jwk = key.to_jwk().unwrap();
+        jwk.as_object_mut().unwrap().remove("d");
+        assert!(matches!(
+            Key::from_jwk(&jwk).unwrap_err().kind(),
+            error::VapidErrorKind::PublicKey
+        ));
+    }
+
+    #[test]
+    fn test_from_jwk_rejects_bad_coordinate_length() {
+        let key = Key::generate().unwrap();
+        let mut jwk = key.to_jwk().unwrap();
+        jwk["x"] = serde_json::Value::from(BASE64_URL_SAFE_NO_PAD.encode([0u8; 31]));
+        assert!(matches!(
+            Key::from_jwk(&jwk).unwrap_err().kind(),
+            error::VapidErrorKind::PublicKey
+        ));
+
+        let mut jwk = key.to_jwk().unwrap();
+        jwk["y"] = serde_json::Value::from(BASE64_URL_SAFE_NO_PAD.encode([0u8; 33]));
+        assert!(matches!(
+            Key::from_jwk(&jwk).unwrap_err().kind(),
+            error::VapidErrorKind::PublicKey
+        ));
+    }
+
     //TODO: Add key input/output tests here.
 }
@@ -0,0 +1,76 @@
+//! Validation rules applied to claims decoded by `verify_with()`.
+//!
+//! `verify()` used to only check the ECDSA signature and hand back whatever
+//! claims were embedded, even an `exp` that had long since passed. A
+//! `Validation` describes which time-based claims to enforce (with leeway,
+//! since clocks drift) and, optionally, which audiences are acceptable.
+
+use std::collections::HashSet;
+
+/// Validation rules for the claims returned by a verified VAPID token.
+#[derive(Debug, Clone)]
+pub struct Validation {
+    /// Reject tokens whose `exp` claim is in the past, or that have no `exp`
+    /// claim at all. Default: `true`.
+    pub validate_exp: bool,
+    /// Reject tokens whose `nbf` claim is in the future. Default: `false`,
+    /// since VAPID tokens don't commonly set `nbf`.
+    pub validate_nbf: bool,
+    /// Reject tokens whose `iat` claim is in the future. Default: `false`.
+    pub validate_iat: bool,
+    /// Seconds of clock-skew tolerance applied to `exp`/`nbf`/`iat` checks.
+    pub leeway: u64,
+    /// If set, the token's `aud` must match one of these values.
+    pub aud: Option<HashSet<String>>,
+}
+
+impl Default for Validation {
+    fn default() -> Self {
+        Validation {
+            validate_exp: true,
+            validate_nbf: false,
+            validate_iat: false,
+            leeway: 0,
+            aud: None,
+        }
+    }
+}
+
+impl Validation {
+    /// A `Validation` with all checks (`exp`, `nbf`, `iat`) enabled and zero leeway.
+    pub fn new() -> Self {
+        Validation {
+            validate_nbf: true,
+            validate_iat: true,
+            ..Validation::default()
+        }
+    }
+
+    /// A `Validation` that performs no checks at all -- matches the historical
+    /// behavior of `verify()`, which only checked the signature.
+    pub fn none() -> Self {
+        Validation {
+            validate_exp: false,
+            validate_nbf: false,
+            validate_iat: false,
+            leeway: 0,
+            aud: None,
+        }
+    }
+
+    /// Set the clock-skew leeway, in seconds.
+    pub fn leeway(mut self, leeway: u64) -> Self {
+        self.leeway = leeway;
+        self
+    }
+
+    /// Require the token's `aud` to match one of `audiences`.
+    pub fn audience<I>(mut self, audiences: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.aud = Some(audiences.into_iter().map(Into::into).collect());
+        self
+    }
+}